@@ -0,0 +1,20 @@
+use serde::Serialize;
+
+/// Structured error type returned by the image-cache commands, so the
+/// frontend can match on `code` instead of parsing free-form strings.
+#[derive(Debug, thiserror::Error, Serialize)]
+#[serde(tag = "code", content = "message")]
+pub(crate) enum CacheError {
+    #[error("file not found in cache: {0}")]
+    NotFound(String),
+    #[error("path escapes the cache directory: {0}")]
+    OutsideCacheDir(String),
+    #[error("io error: {0}")]
+    Io(String),
+    #[error("failed to decode base64: {0}")]
+    Base64Decode(String),
+    #[error("failed to download image: {0}")]
+    Download(String),
+    #[error("failed to generate thumbnail: {0}")]
+    Image(String),
+}
@@ -1,11 +1,149 @@
-use std::path::PathBuf;
+mod error;
+mod manifest;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::fs;
 use base64::{Engine as _, engine::general_purpose};
+use serde::Serialize;
 use tauri::Manager;
+use tokio::sync::{Mutex, Semaphore};
+
+use error::CacheError;
+use manifest::CacheManifest;
+
+// Cache is trimmed back under this size, in bytes, whenever a write pushes it over.
+const DEFAULT_MAX_CACHE_BYTES: u64 = 500 * 1024 * 1024;
 
 // Application state to store paths
 struct AppState {
     image_cache_dir: PathBuf,
+    // One semaphore per in-flight download URL so concurrent requests for the
+    // same remote image wait on a single fetch instead of racing each other.
+    download_locks: Mutex<HashMap<String, Arc<Semaphore>>>,
+    manifest: Mutex<CacheManifest>,
+    // Runtime-configurable via `set_max_cache_bytes` so the settings UI can
+    // raise or lower the limit without an app restart.
+    max_cache_bytes: AtomicU64,
+}
+
+fn now_unix() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+// Records `filename` (sourced from `source`, `size_bytes` long) in the
+// manifest, deletes least-recently-used entries until the cache is back under
+// `max_cache_bytes`, then persists the manifest.
+async fn record_and_evict(
+    state: &AppState,
+    filename: &str,
+    source: &str,
+    size_bytes: u64,
+) -> Result<(), CacheError> {
+    let mut manifest = state.manifest.lock().await;
+    manifest.record(filename, source, size_bytes, now_unix());
+
+    for victim in manifest.entries_to_evict(state.max_cache_bytes.load(Ordering::Relaxed)) {
+        let _ = fs::remove_file(state.image_cache_dir.join(&victim));
+        manifest.remove(&victim);
+    }
+
+    manifest.save(&state.image_cache_dir)
+}
+
+// Compute the md5 hex digest of a URL, used as the content-addressed cache key.
+fn url_cache_key(url: &str) -> String {
+    format!("{:x}", md5::compute(url.as_bytes()))
+}
+
+// Best-effort extension sniffed from the URL path, falling back to `bin`.
+fn url_extension(url: &str) -> String {
+    url.rsplit('/')
+        .next()
+        .map(|last| last.split(['?', '#']).next().unwrap_or(last))
+        .and_then(|path_only| path_only.rsplit_once('.'))
+        .map(|(_, ext)| ext.to_string())
+        .filter(|ext| !ext.is_empty())
+        .unwrap_or_else(|| "bin".to_string())
+}
+
+// Resolves `filename` to a path inside `cache_dir`, rejecting anything that
+// contains a path separator or a parent-directory component and refusing to
+// hand back a path that canonicalizes outside the cache directory.
+fn sanitize_cache_filename(cache_dir: &Path, filename: &str) -> Result<PathBuf, CacheError> {
+    if filename.is_empty()
+        || filename.contains('/')
+        || filename.contains('\\')
+        || Path::new(filename)
+            .components()
+            .any(|c| !matches!(c, std::path::Component::Normal(_)))
+    {
+        return Err(CacheError::OutsideCacheDir(filename.to_string()));
+    }
+
+    let candidate = cache_dir.join(filename);
+
+    let canonical_dir = cache_dir
+        .canonicalize()
+        .map_err(|e| CacheError::Io(e.to_string()))?;
+
+    // The file itself may not exist yet (e.g. before a write), so only an
+    // existing entry (including a dangling symlink) is canonicalized and
+    // checked to still resolve inside the cache directory.
+    if candidate.symlink_metadata().is_ok() {
+        let canonical_candidate = candidate
+            .canonicalize()
+            .map_err(|_| CacheError::OutsideCacheDir(filename.to_string()))?;
+        if !canonical_candidate.starts_with(&canonical_dir) {
+            return Err(CacheError::OutsideCacheDir(filename.to_string()));
+        }
+    }
+
+    Ok(candidate)
+}
+
+// Same traversal guard as `sanitize_cache_filename`, but for relative paths
+// that may have more than one component (e.g. `thumbnails/foo_128.png`), as
+// served through the `cache://` scheme. Any `..`/root/prefix component is
+// rejected up front, and the closest existing ancestor of the candidate is
+// canonicalized and checked to still be rooted under `cache_dir`.
+fn sanitize_cache_relative_path(cache_dir: &Path, relative_path: &str) -> Result<PathBuf, CacheError> {
+    if relative_path.is_empty()
+        || Path::new(relative_path)
+            .components()
+            .any(|c| !matches!(c, std::path::Component::Normal(_)))
+    {
+        return Err(CacheError::OutsideCacheDir(relative_path.to_string()));
+    }
+
+    let candidate = cache_dir.join(relative_path);
+
+    let canonical_dir = cache_dir
+        .canonicalize()
+        .map_err(|e| CacheError::Io(e.to_string()))?;
+
+    let mut ancestor = candidate.as_path();
+    let canonical_ancestor = loop {
+        if let Ok(canon) = ancestor.canonicalize() {
+            break canon;
+        }
+        match ancestor.parent() {
+            Some(parent) => ancestor = parent,
+            None => return Err(CacheError::OutsideCacheDir(relative_path.to_string())),
+        }
+    };
+
+    if !canonical_ancestor.starts_with(&canonical_dir) {
+        return Err(CacheError::OutsideCacheDir(relative_path.to_string()));
+    }
+
+    Ok(candidate)
 }
 
 #[tauri::command]
@@ -23,21 +161,24 @@ async fn save_image_to_cache(
     filename: String,
     base64_data: String,
     state: tauri::State<'_, AppState>
-) -> Result<String, String> {
+) -> Result<String, CacheError> {
     let cache_dir = &state.image_cache_dir;
 
     // Create cache directory if it doesn't exist
     fs::create_dir_all(cache_dir)
-        .map_err(|e| format!("Failed to create cache directory: {}", e))?;
+        .map_err(|e| CacheError::Io(e.to_string()))?;
 
-    let file_path = cache_dir.join(&filename);
+    let file_path = sanitize_cache_filename(cache_dir, &filename)?;
 
     // Decode base64 and write to file
     let image_bytes = general_purpose::STANDARD.decode(&base64_data)
-        .map_err(|e| format!("Failed to decode base64: {}", e))?;
+        .map_err(|e| CacheError::Base64Decode(e.to_string()))?;
+    let size_bytes = image_bytes.len() as u64;
 
     fs::write(&file_path, image_bytes)
-        .map_err(|e| format!("Failed to write image file: {}", e))?;
+        .map_err(|e| CacheError::Io(e.to_string()))?;
+
+    record_and_evict(&state, &filename, "local", size_bytes).await?;
 
     Ok(filename)
 }
@@ -46,34 +187,257 @@ async fn save_image_to_cache(
 async fn get_image_cache_path(
     filename: String,
     state: tauri::State<'_, AppState>
-) -> Result<String, String> {
-    let file_path = state.image_cache_dir.join(&filename);
-        
+) -> Result<String, CacheError> {
+    let file_path = sanitize_cache_filename(&state.image_cache_dir, &filename)?;
+
     println!("{}",file_path.display());
 
     // Check if file exists
     if !file_path.exists() {
-        return Err(format!("Image not found in cache: {}", filename));
+        return Err(CacheError::NotFound(filename));
+    }
+
+    {
+        let mut manifest = state.manifest.lock().await;
+        manifest.touch(&filename, now_unix());
+        manifest.persist_in_background(state.image_cache_dir.clone());
+    }
+
+    Ok(file_path.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+async fn proxy_remote_image(
+    url: String,
+    state: tauri::State<'_, AppState>
+) -> Result<String, CacheError> {
+    let cache_dir = &state.image_cache_dir;
+
+    fs::create_dir_all(cache_dir)
+        .map_err(|e| CacheError::Io(e.to_string()))?;
+
+    let filename = format!("{}.{}", url_cache_key(&url), url_extension(&url));
+    let file_path = cache_dir.join(&filename);
+
+    if file_path.exists() {
+        let mut manifest = state.manifest.lock().await;
+        manifest.touch(&filename, now_unix());
+        manifest.persist_in_background(cache_dir.clone());
+        return Ok(file_path.to_string_lossy().to_string());
+    }
+
+    // Only one download per URL runs at a time; everyone else waits on the
+    // same permit and then finds the file already on disk.
+    let permit = {
+        let mut locks = state.download_locks.lock().await;
+        locks
+            .entry(url.clone())
+            .or_insert_with(|| Arc::new(Semaphore::new(1)))
+            .clone()
+    };
+    let _permit = permit.acquire().await.map_err(|e| CacheError::Io(e.to_string()))?;
+
+    // Whether this succeeds or fails, the semaphore entry for `url` must be
+    // dropped afterwards or a run of failing downloads leaks one map entry
+    // per distinct URL for the life of the process.
+    let result = download_and_cache_remote_image(&state, &url, &filename, &file_path).await;
+
+    drop(_permit);
+    state.download_locks.lock().await.remove(&url);
+
+    result
+}
+
+async fn download_and_cache_remote_image(
+    state: &AppState,
+    url: &str,
+    filename: &str,
+    file_path: &std::path::Path,
+) -> Result<String, CacheError> {
+    if file_path.exists() {
+        return Ok(file_path.to_string_lossy().to_string());
     }
 
+    let response = reqwest::get(url)
+        .await
+        .map_err(|e| CacheError::Download(e.to_string()))?;
+
+    let bytes = response
+        .bytes()
+        .await
+        .map_err(|e| CacheError::Download(e.to_string()))?;
+    let size_bytes = bytes.len() as u64;
+
+    fs::write(file_path, &bytes)
+        .map_err(|e| CacheError::Io(e.to_string()))?;
+
+    record_and_evict(state, filename, url, size_bytes).await?;
+
     Ok(file_path.to_string_lossy().to_string())
 }
 
+#[tauri::command]
+async fn get_thumbnail(
+    filename: String,
+    max_edge: u32,
+    state: tauri::State<'_, AppState>
+) -> Result<String, CacheError> {
+    let cache_dir = state.image_cache_dir.clone();
+    let source_path = sanitize_cache_filename(&cache_dir, &filename)?;
+
+    if !source_path.exists() {
+        return Err(CacheError::NotFound(filename));
+    }
+
+    let thumbnails_dir = cache_dir.join("thumbnails");
+    fs::create_dir_all(&thumbnails_dir).map_err(|e| CacheError::Io(e.to_string()))?;
+
+    let ext = Path::new(&filename)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("bin");
+    let stem = Path::new(&filename)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or(&filename);
+    // Stored relative to `image_cache_dir` (not `thumbnails_dir`) so it can
+    // double as the manifest key and be found by clear_image_cache's lookup.
+    let manifest_key = format!("thumbnails/{}_{}.{}", stem, max_edge, ext);
+    let thumb_path = cache_dir.join(&manifest_key);
+
+    if thumb_path.exists() {
+        let mut manifest = state.manifest.lock().await;
+        manifest.touch(&manifest_key, now_unix());
+        manifest.persist_in_background(cache_dir.clone());
+        return Ok(thumb_path.to_string_lossy().to_string());
+    }
+
+    // Decoding and resizing is CPU-bound, so run it on the blocking pool and
+    // keep the async runtime free to serve other commands in the meantime.
+    let thumb_path_for_task = thumb_path.clone();
+    let size_bytes = tokio::task::spawn_blocking(move || -> Result<u64, CacheError> {
+        let source_image = image::open(&source_path).map_err(|e| CacheError::Image(e.to_string()))?;
+        source_image
+            .thumbnail(max_edge, max_edge)
+            .save(&thumb_path_for_task)
+            .map_err(|e| CacheError::Image(e.to_string()))?;
+
+        fs::metadata(&thumb_path_for_task)
+            .map(|metadata| metadata.len())
+            .map_err(|e| CacheError::Io(e.to_string()))
+    })
+    .await
+    .map_err(|e| CacheError::Image(e.to_string()))??;
+
+    record_and_evict(&state, &manifest_key, &format!("thumbnail:{}:{}", filename, max_edge), size_bytes).await?;
+
+    Ok(thumb_path.to_string_lossy().to_string())
+}
+
+#[derive(Serialize)]
+struct CacheStats {
+    count: usize,
+    total_bytes: u64,
+}
+
+#[tauri::command]
+async fn clear_image_cache(state: tauri::State<'_, AppState>) -> Result<(), CacheError> {
+    let mut manifest = state.manifest.lock().await;
+
+    for filename in manifest.filenames() {
+        let _ = fs::remove_file(state.image_cache_dir.join(&filename));
+    }
+    manifest.clear();
+    manifest.save(&state.image_cache_dir)
+}
+
+#[tauri::command]
+async fn get_cache_stats(state: tauri::State<'_, AppState>) -> Result<CacheStats, CacheError> {
+    let manifest = state.manifest.lock().await;
+    Ok(CacheStats {
+        count: manifest.count(),
+        total_bytes: manifest.total_bytes(),
+    })
+}
+
+#[tauri::command]
+async fn set_max_cache_bytes(max_bytes: u64, state: tauri::State<'_, AppState>) -> Result<(), CacheError> {
+    state.max_cache_bytes.store(max_bytes, Ordering::Relaxed);
+    Ok(())
+}
+
+// Serves `cache://<filename>` by reading the file straight out of
+// `image_cache_dir`, so the frontend can point an `<img>` tag at a cached
+// image without ever base64-encoding it over the IPC bridge. `filename` may
+// be nested (e.g. `thumbnails/foo_128.png`), in which case the URI parser
+// puts the first segment in the authority and the rest in the path, so both
+// have to be combined to recover the real relative path.
+fn handle_cache_protocol(
+    ctx: tauri::UriSchemeContext<'_, tauri::Wry>,
+    request: tauri::http::Request<Vec<u8>>,
+) -> tauri::http::Response<Vec<u8>> {
+    let state = ctx.app_handle().state::<AppState>();
+    let host = request.uri().host().unwrap_or_default();
+    let path = request.uri().path().trim_start_matches('/');
+
+    let relative_path = match (host.is_empty(), path.is_empty()) {
+        (true, _) => path.to_string(),
+        (false, true) => host.to_string(),
+        (false, false) => format!("{}/{}", host, path),
+    };
+
+    let file_path = match sanitize_cache_relative_path(&state.image_cache_dir, &relative_path) {
+        Ok(path) => path,
+        Err(_) => {
+            return tauri::http::Response::builder()
+                .status(404)
+                .body(Vec::new())
+                .unwrap();
+        }
+    };
+
+    match fs::read(&file_path) {
+        Ok(bytes) => {
+            let mime = mime_guess::from_path(&file_path)
+                .first_or_octet_stream()
+                .to_string();
+
+            tauri::http::Response::builder()
+                .status(200)
+                .header("Content-Type", mime)
+                .body(bytes)
+                .unwrap()
+        }
+        Err(_) => tauri::http::Response::builder()
+            .status(404)
+            .body(Vec::new())
+            .unwrap(),
+    }
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_opener::init())
+        .register_uri_scheme_protocol("cache", handle_cache_protocol)
         .setup(|app| {
             // Get app data directory from Tauri (cross-platform)
             let app_data_dir = app.path().app_local_data_dir()
                 .expect("Failed to get app data directory");
 
             let image_cache_dir = app_data_dir.join("image_cache");
+            fs::create_dir_all(&image_cache_dir)
+                .expect("Failed to create image cache directory");
+
+            let manifest = CacheManifest::load(&image_cache_dir);
 
             // Store in managed state
             app.manage(AppState {
                 image_cache_dir,
+                download_locks: Mutex::new(HashMap::new()),
+                manifest: Mutex::new(manifest),
+                max_cache_bytes: AtomicU64::new(DEFAULT_MAX_CACHE_BYTES),
             });
 
             Ok(())
@@ -81,7 +445,12 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             read_file_as_base64,
             save_image_to_cache,
-            get_image_cache_path
+            get_image_cache_path,
+            proxy_remote_image,
+            clear_image_cache,
+            get_cache_stats,
+            set_max_cache_bytes,
+            get_thumbnail
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
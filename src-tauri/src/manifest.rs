@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::CacheError;
+
+const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// Metadata tracked per cached file so the cache can report its size and
+/// evict the least-recently-used entries once it grows past its limit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct ManifestEntry {
+    pub source: String,
+    pub size_bytes: u64,
+    pub last_accessed: u64,
+}
+
+/// On-disk record of everything currently in `image_cache_dir`, keyed by
+/// path relative to that directory (a bare filename for originals,
+/// `thumbnails/...` for thumbnails). Persisted as JSON alongside the cached
+/// files themselves.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct CacheManifest {
+    entries: HashMap<String, ManifestEntry>,
+}
+
+impl CacheManifest {
+    pub(crate) fn load(cache_dir: &Path) -> Self {
+        fs::read_to_string(cache_dir.join(MANIFEST_FILE_NAME))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn save(&self, cache_dir: &Path) -> Result<(), CacheError> {
+        let data = serde_json::to_string_pretty(self).map_err(|e| CacheError::Io(e.to_string()))?;
+        fs::write(cache_dir.join(MANIFEST_FILE_NAME), data).map_err(|e| CacheError::Io(e.to_string()))
+    }
+
+    /// Fire-and-forget persist for hot paths (cache hits, where only an
+    /// in-memory `touch` actually needs to happen synchronously): serializes
+    /// now, then writes the file on the blocking pool so a per-hit touch
+    /// never stalls the async executor on a full-manifest rewrite.
+    pub(crate) fn persist_in_background(&self, cache_dir: PathBuf) {
+        if let Ok(data) = serde_json::to_string_pretty(self) {
+            tokio::task::spawn_blocking(move || {
+                let _ = fs::write(cache_dir.join(MANIFEST_FILE_NAME), data);
+            });
+        }
+    }
+
+    pub(crate) fn record(&mut self, filename: &str, source: &str, size_bytes: u64, now: u64) {
+        self.entries.insert(
+            filename.to_string(),
+            ManifestEntry { source: source.to_string(), size_bytes, last_accessed: now },
+        );
+    }
+
+    pub(crate) fn touch(&mut self, filename: &str, now: u64) {
+        if let Some(entry) = self.entries.get_mut(filename) {
+            entry.last_accessed = now;
+        }
+    }
+
+    pub(crate) fn remove(&mut self, filename: &str) {
+        self.entries.remove(filename);
+    }
+
+    pub(crate) fn filenames(&self) -> Vec<String> {
+        self.entries.keys().cloned().collect()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub(crate) fn count(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub(crate) fn total_bytes(&self) -> u64 {
+        self.entries.values().map(|e| e.size_bytes).sum()
+    }
+
+    /// Picks the least-recently-accessed entries to delete until the total
+    /// cache size is at or under `max_bytes`, without actually removing them.
+    pub(crate) fn entries_to_evict(&self, max_bytes: u64) -> Vec<String> {
+        let mut total = self.total_bytes();
+        if total <= max_bytes {
+            return Vec::new();
+        }
+
+        let mut by_age: Vec<_> = self.entries.iter().collect();
+        by_age.sort_by_key(|(_, entry)| entry.last_accessed);
+
+        let mut victims = Vec::new();
+        for (filename, entry) in by_age {
+            if total <= max_bytes {
+                break;
+            }
+            victims.push(filename.clone());
+            total = total.saturating_sub(entry.size_bytes);
+        }
+        victims
+    }
+}